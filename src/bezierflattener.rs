@@ -0,0 +1,172 @@
+//! A small, self-contained cubic bezier flattener.
+//!
+//! The API (`CBezier`, `CFlatteningSink`, `CBezierFlattener`) mirrors the
+//! shape of the flattener used by WPF's software rasterizer: a curve is fed
+//! to a `CBezierFlattener`, which recursively subdivides it until each piece
+//! is within `tolerance` of a straight line, reporting the resulting points
+//! (and, optionally, the curve's tangent at each of them) to a
+//! `CFlatteningSink`.
+
+// Mirrors the original WPF API's naming (`HRESULT`, `fLast`, `fWithTangents`,
+// ...) rather than renaming everything to Rust conventions.
+#![allow(non_snake_case, clippy::upper_case_acronyms)]
+
+pub type HRESULT = i32;
+pub const S_OK: HRESULT = 0;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct GpPointR {
+    pub x: f64,
+    pub y: f64,
+}
+
+impl GpPointR {
+    fn new(x: f64, y: f64) -> Self {
+        GpPointR { x, y }
+    }
+}
+
+impl std::ops::Sub for GpPointR {
+    type Output = GpPointR;
+    fn sub(self, other: GpPointR) -> GpPointR {
+        GpPointR::new(self.x - other.x, self.y - other.y)
+    }
+}
+
+impl std::ops::Add for GpPointR {
+    type Output = GpPointR;
+    fn add(self, other: GpPointR) -> GpPointR {
+        GpPointR::new(self.x + other.x, self.y + other.y)
+    }
+}
+
+impl std::ops::Mul<f64> for GpPointR {
+    type Output = GpPointR;
+    fn mul(self, s: f64) -> GpPointR {
+        GpPointR::new(self.x * s, self.y * s)
+    }
+}
+
+fn mid(a: GpPointR, b: GpPointR) -> GpPointR {
+    GpPointR::new((a.x + b.x) / 2., (a.y + b.y) / 2.)
+}
+
+/// A cubic bezier curve defined by its four control points.
+pub struct CBezier {
+    points: [GpPointR; 4],
+}
+
+impl CBezier {
+    pub fn new(points: [GpPointR; 4]) -> CBezier {
+        CBezier { points }
+    }
+
+    fn tangent_at(&self, t: f64) -> GpPointR {
+        let p = &self.points;
+        let u = 1. - t;
+        (p[1] - p[0]) * (3. * u * u) + (p[2] - p[1]) * (6. * u * t) + (p[3] - p[2]) * (3. * t * t)
+    }
+
+    /// A cheap, conservative estimate of how far the curve's control polygon
+    /// deviates from a straight line between its endpoints.
+    fn flatness_squared(&self) -> f64 {
+        let p = &self.points;
+        let ux = 3. * p[1].x - 2. * p[0].x - p[3].x;
+        let uy = 3. * p[1].y - 2. * p[0].y - p[3].y;
+        let vx = 3. * p[2].x - 2. * p[3].x - p[0].x;
+        let vy = 3. * p[2].y - 2. * p[3].y - p[0].y;
+        let ux = ux.abs().max(vx.abs());
+        let uy = uy.abs().max(vy.abs());
+        ux * ux + uy * uy
+    }
+
+    /// Splits the curve at `t = 0.5` via de Casteljau's algorithm.
+    fn subdivide(&self) -> (CBezier, CBezier) {
+        let p = &self.points;
+        let p01 = mid(p[0], p[1]);
+        let p12 = mid(p[1], p[2]);
+        let p23 = mid(p[2], p[3]);
+        let p012 = mid(p01, p12);
+        let p123 = mid(p12, p23);
+        let p0123 = mid(p012, p123);
+        (
+            CBezier::new([p[0], p01, p012, p0123]),
+            CBezier::new([p0123, p123, p23, p[3]]),
+        )
+    }
+}
+
+/// Receives the points produced by flattening a `CBezier`.
+pub trait CFlatteningSink {
+    /// Called for each flattened point when `Flatten(true)` is used.
+    /// `vec` is the curve's tangent at `pt`; `fLast` is set on the curve's
+    /// final point.
+    fn AcceptPointAndTangent(&mut self, pt: &GpPointR, vec: &GpPointR, fLast: bool) -> HRESULT;
+
+    /// Called for each flattened point when `Flatten(false)` is used.
+    /// The sink may abort the flattening early by setting `fAborted`.
+    fn AcceptPoint(&mut self, pt: &GpPointR, t: f64, fAborted: &mut bool) -> HRESULT;
+}
+
+/// A curve can't be subdivided more than this many times, guarding against
+/// runaway recursion on a degenerate (e.g. cusped) curve.
+const MAX_DEPTH: u32 = 32;
+
+/// Flattens a `CBezier` into a polyline that stays within `tolerance` of the
+/// true curve, feeding the result to a `CFlatteningSink`.
+pub struct CBezierFlattener<'a> {
+    bezier: &'a CBezier,
+    sink: &'a mut dyn CFlatteningSink,
+    tolerance: f64,
+    aborted: bool,
+}
+
+impl<'a> CBezierFlattener<'a> {
+    pub fn new(bezier: &'a CBezier, sink: &'a mut dyn CFlatteningSink, tolerance: f64) -> Self {
+        CBezierFlattener {
+            bezier,
+            sink,
+            tolerance,
+            aborted: false,
+        }
+    }
+
+    /// Flattens the curve. When `fWithTangents` is set, each point is
+    /// reported via `AcceptPointAndTangent` along with the curve's tangent
+    /// there; otherwise `AcceptPoint` is used.
+    pub fn Flatten(&mut self, fWithTangents: bool) -> HRESULT {
+        let tolerance_squared = self.tolerance * self.tolerance;
+        self.aborted = false;
+        let whole = CBezier::new(self.bezier.points);
+        self.flatten_segment(&whole, 0., 1., tolerance_squared, fWithTangents, 0)
+    }
+
+    fn flatten_segment(
+        &mut self,
+        segment: &CBezier,
+        t0: f64,
+        t1: f64,
+        tolerance_squared: f64,
+        fWithTangents: bool,
+        depth: u32,
+    ) -> HRESULT {
+        if depth < MAX_DEPTH && segment.flatness_squared() > tolerance_squared {
+            let (left, right) = segment.subdivide();
+            let tmid = (t0 + t1) / 2.;
+            let hr = self.flatten_segment(&left, t0, tmid, tolerance_squared, fWithTangents, depth + 1);
+            if hr != S_OK || self.aborted {
+                return hr;
+            }
+            return self.flatten_segment(&right, tmid, t1, tolerance_squared, fWithTangents, depth + 1);
+        }
+
+        let pt = segment.points[3];
+        let fLast = t1 >= 1.;
+        if fWithTangents {
+            let tangent = self.bezier.tangent_at(t1);
+            self.sink.AcceptPointAndTangent(&pt, &tangent, fLast)
+        } else {
+            self.sink.AcceptPoint(&pt, t1, &mut self.aborted)
+        }
+    }
+}
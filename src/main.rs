@@ -51,7 +51,7 @@ pub type Vector = euclid::default::Vector2D<f32>;
 
 
 
-#[derive(Clone, PartialEq, Debug)]
+#[derive(Debug)]
 pub struct StrokeStyle {
     pub width: f32,
     pub cap: LineCap,
@@ -59,6 +59,15 @@ pub struct StrokeStyle {
     pub miter_limit: f32,
     pub dash_array: Vec<f32>,
     pub dash_offset: f32,
+    /// Maximum distance, in path units, that the flattened approximation of
+    /// a `QuadTo`/`CubicTo` curve is allowed to deviate from the true curve.
+    pub flatten_tolerance: f32,
+    /// Overrides `cap` with a custom cap decoration (e.g. an arrowhead).
+    /// `None` falls back to the builtin decoration for `cap`.
+    pub capper: Option<Box<dyn Capper>>,
+    /// Overrides `join` with a custom join decoration. `None` falls back to
+    /// the builtin decoration for `join`.
+    pub joiner: Option<Box<dyn Joiner>>,
 }
 
 impl Default for StrokeStyle {
@@ -70,10 +79,43 @@ impl Default for StrokeStyle {
             miter_limit: 10.,
             dash_array: Vec::new(),
             dash_offset: 0.,
+            flatten_tolerance: 0.1,
+            capper: None,
+            joiner: None,
         }
     }
 }
 
+impl Clone for StrokeStyle {
+    fn clone(&self) -> Self {
+        StrokeStyle {
+            width: self.width,
+            cap: self.cap,
+            join: self.join,
+            miter_limit: self.miter_limit,
+            dash_array: self.dash_array.clone(),
+            dash_offset: self.dash_offset,
+            flatten_tolerance: self.flatten_tolerance,
+            capper: self.capper.as_ref().map(|c| c.clone_box()),
+            joiner: self.joiner.as_ref().map(|j| j.clone_box()),
+        }
+    }
+}
+
+impl PartialEq for StrokeStyle {
+    /// Custom `capper`/`joiner` trait objects aren't compared for equality;
+    /// only the builtin, enum-driven parts of the style are.
+    fn eq(&self, other: &Self) -> bool {
+        self.width == other.width
+            && self.cap == other.cap
+            && self.join == other.join
+            && self.miter_limit == other.miter_limit
+            && self.dash_array == other.dash_array
+            && self.dash_offset == other.dash_offset
+            && self.flatten_tolerance == other.flatten_tolerance
+    }
+}
+
 /// A helper struct used for constructing a `Path`.
 pub struct PathBuilder {
     path: Path,
@@ -288,36 +330,216 @@ fn join_round(path: &mut PathBuilder, center: Point, a: Vector, b: Vector, radiu
     arc(path, center.x, center.y, radius, a, b);
 }
 
+/// Draws the cap decoration at a subpath's end. Implement this to add a
+/// custom cap (e.g. an arrowhead) and set it as a `StrokeStyle`'s `capper`.
+pub trait Capper: std::fmt::Debug {
+    fn cap(&self, dest: &mut PathBuilder, pivot: Point, half_width: f32, normal: Vector);
+    /// Like `cap`, but returns the cap decoration as a polygon point list
+    /// instead of emitting it into a `PathBuilder`, for callers building a
+    /// coverage mesh (see `stroke_to_aa_vertices`) rather than a fill path.
+    /// The second element marks, for each `i`, whether the edge
+    /// `points[i] -> points[(i + 1) % points.len()]` lies on the stroke's
+    /// true outer boundary; edges that instead coincide with the
+    /// neighboring segment quad's cross-section (e.g. the radii of a round
+    /// cap, or the edge at `pivot` itself) are `false` so they aren't
+    /// feathered twice.
+    fn cap_points(&self, pivot: Point, half_width: f32, normal: Vector) -> (Vec<Point>, Vec<bool>);
+    fn clone_box(&self) -> Box<dyn Capper>;
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct ButtCap;
+impl Capper for ButtCap {
+    fn cap(&self, _dest: &mut PathBuilder, _pivot: Point, _half_width: f32, _normal: Vector) {
+        /* nothing to do */
+    }
+    fn cap_points(&self, _pivot: Point, _half_width: f32, _normal: Vector) -> (Vec<Point>, Vec<bool>) {
+        (Vec::new(), Vec::new())
+    }
+    fn clone_box(&self) -> Box<dyn Capper> {
+        Box::new(*self)
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct RoundCap;
+impl Capper for RoundCap {
+    fn cap(&self, dest: &mut PathBuilder, pivot: Point, half_width: f32, normal: Vector) {
+        dest.arc_wedge(pivot, half_width, normal, flip(normal));
+    }
+    fn cap_points(&self, pivot: Point, half_width: f32, normal: Vector) -> (Vec<Point>, Vec<bool>) {
+        let points = arc_wedge_points(pivot, half_width, normal, flip(normal));
+        let mask = arc_wedge_exterior_mask(points.len());
+        (points, mask)
+    }
+    fn clone_box(&self) -> Box<dyn Capper> {
+        Box::new(*self)
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct SquareCap;
+impl Capper for SquareCap {
+    fn cap(&self, dest: &mut PathBuilder, pivot: Point, half_width: f32, normal: Vector) {
+        // parallel vector
+        let v = Vector::new(normal.y, -normal.x);
+        let end = pivot + v * half_width;
+        dest.quad(pivot.x + normal.x * half_width, pivot.y + normal.y * half_width,
+        end.x + normal.x * half_width, end.y + normal.y * half_width,
+        end.x + -normal.x * half_width, end.y + -normal.y * half_width,
+        pivot.x - normal.x * half_width, pivot.y - normal.y * half_width);
+    }
+    fn cap_points(&self, pivot: Point, half_width: f32, normal: Vector) -> (Vec<Point>, Vec<bool>) {
+        let v = Vector::new(normal.y, -normal.x);
+        let end = pivot + v * half_width;
+        let points = vec![
+            pivot + normal * half_width,
+            end + normal * half_width,
+            end - normal * half_width,
+            pivot - normal * half_width,
+        ];
+        // The last edge, back to `pivot + normal * half_width`, sits at
+        // `pivot` and coincides with the adjoining segment quad's
+        // cross-section edge there.
+        (points, vec![true, true, true, false])
+    }
+    fn clone_box(&self) -> Box<dyn Capper> {
+        Box::new(*self)
+    }
+}
+
+fn default_capper(cap: LineCap) -> Box<dyn Capper> {
+    match cap {
+        LineCap::Butt => Box::new(ButtCap),
+        LineCap::Round => Box::new(RoundCap),
+        LineCap::Square => Box::new(SquareCap),
+    }
+}
+
 fn cap_line(dest: &mut PathBuilder, style: &StrokeStyle, pt: Point, normal: Vector) {
-    let offset = style.width / 2.;
-    match style.cap {
-        LineCap::Butt => { /* nothing to do */ }
-        LineCap::Round => {
-            dest.arc_wedge(pt, offset, normal, flip(normal));
+    let half_width = style.width / 2.;
+    match &style.capper {
+        Some(capper) => capper.cap(dest, pt, half_width, normal),
+        None => default_capper(style.cap).cap(dest, pt, half_width, normal),
+    }
+}
+
+/// Draws the join decoration between two stroked segments. Implement this to
+/// add a custom join (e.g. a triangular "kite" join) and set it as a
+/// `StrokeStyle`'s `joiner`.
+pub trait Joiner: std::fmt::Debug {
+    fn join(&self, dest: &mut PathBuilder, pivot: Point, half_width: f32, s1_normal: Vector, s2_normal: Vector);
+    /// Like `join`, but returns the join decoration as a polygon point list
+    /// instead of emitting it into a `PathBuilder`, for callers building a
+    /// coverage mesh (see `stroke_to_aa_vertices`) rather than a fill path.
+    /// The second element marks, for each `i`, whether the edge
+    /// `points[i] -> points[(i + 1) % points.len()]` lies on the stroke's
+    /// true outer boundary; edges that instead coincide with one of the two
+    /// adjoining segment quads' cross-sections (e.g. the radii of a round
+    /// join, or the two edges meeting at `pivot`) are `false` so they aren't
+    /// feathered twice.
+    fn join_points(&self, pivot: Point, half_width: f32, s1_normal: Vector, s2_normal: Vector) -> (Vec<Point>, Vec<bool>);
+    fn clone_box(&self) -> Box<dyn Joiner>;
+}
+
+fn bevel_join(dest: &mut PathBuilder, pivot: Point, half_width: f32, s1_normal: Vector, s2_normal: Vector) {
+    dest.tri(pivot.x + s1_normal.x * half_width, pivot.y + s1_normal.y * half_width,
+          pivot.x + s2_normal.x * half_width, pivot.y + s2_normal.y * half_width,
+          pivot.x, pivot.y);
+}
+
+/// The bevel triangle `[pivot + s1_normal * half_width, pivot + s2_normal *
+/// half_width, pivot]`, and its exterior-edge mask: only the bevel face
+/// (the first edge) is on the stroke's true outer boundary; the other two
+/// edges meet at `pivot` and coincide with the adjoining segment quads'
+/// cross-sections.
+fn bevel_points(pivot: Point, half_width: f32, s1_normal: Vector, s2_normal: Vector) -> (Vec<Point>, Vec<bool>) {
+    (vec![pivot + s1_normal * half_width, pivot + s2_normal * half_width, pivot], vec![true, false, false])
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct RoundJoin;
+impl Joiner for RoundJoin {
+    fn join(&self, dest: &mut PathBuilder, pivot: Point, half_width: f32, s1_normal: Vector, s2_normal: Vector) {
+        let (s1_normal, s2_normal) = canonicalize_join_normals(s1_normal, s2_normal);
+        dest.arc_wedge(pivot, half_width, s1_normal, s2_normal);
+    }
+    fn join_points(&self, pivot: Point, half_width: f32, s1_normal: Vector, s2_normal: Vector) -> (Vec<Point>, Vec<bool>) {
+        let (s1_normal, s2_normal) = canonicalize_join_normals(s1_normal, s2_normal);
+        let points = arc_wedge_points(pivot, half_width, s1_normal, s2_normal);
+        let mask = arc_wedge_exterior_mask(points.len());
+        (points, mask)
+    }
+    fn clone_box(&self) -> Box<dyn Joiner> {
+        Box::new(*self)
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct BevelJoin;
+impl Joiner for BevelJoin {
+    fn join(&self, dest: &mut PathBuilder, pivot: Point, half_width: f32, s1_normal: Vector, s2_normal: Vector) {
+        let (s1_normal, s2_normal) = canonicalize_join_normals(s1_normal, s2_normal);
+        bevel_join(dest, pivot, half_width, s1_normal, s2_normal);
+    }
+    fn join_points(&self, pivot: Point, half_width: f32, s1_normal: Vector, s2_normal: Vector) -> (Vec<Point>, Vec<bool>) {
+        let (s1_normal, s2_normal) = canonicalize_join_normals(s1_normal, s2_normal);
+        bevel_points(pivot, half_width, s1_normal, s2_normal)
+    }
+    fn clone_box(&self) -> Box<dyn Joiner> {
+        Box::new(*self)
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct MiterJoin {
+    pub limit: f32,
+}
+impl Joiner for MiterJoin {
+    fn join(&self, dest: &mut PathBuilder, pivot: Point, half_width: f32, s1_normal: Vector, s2_normal: Vector) {
+        let (s1_normal, s2_normal) = canonicalize_join_normals(s1_normal, s2_normal);
+        let in_dot_out = -s1_normal.x * s2_normal.x + -s1_normal.y * s2_normal.y;
+        if 2. <= self.limit * self.limit * (1. - in_dot_out) {
+            let start = pivot + s1_normal * half_width;
+            let end = pivot + s2_normal * half_width;
+            if let Some(intersection) = line_intersection(start, s1_normal, end, s2_normal) {
+                // We won't have an intersection if the segments are parallel
+                dest.quad(pivot.x + s1_normal.x * half_width, pivot.y + s1_normal.y * half_width,
+                intersection.x, intersection.y,
+                pivot.x + s2_normal.x * half_width, pivot.y + s2_normal.y * half_width,
+                pivot.x, pivot.y);
+                return;
+            }
         }
-        LineCap::Square => {
-            // parallel vector
-            let v = Vector::new(normal.y, -normal.x);
-            let end = pt + v * offset;
-            dest.quad(pt.x + normal.x * offset, pt.y + normal.y * offset,
-            end.x + normal.x * offset, end.y + normal.y * offset,
-            end.x + -normal.x * offset, end.y + -normal.y * offset,
-            pt.x - normal.x * offset, pt.y - normal.y * offset);
+        bevel_join(dest, pivot, half_width, s1_normal, s2_normal);
+    }
+    fn join_points(&self, pivot: Point, half_width: f32, s1_normal: Vector, s2_normal: Vector) -> (Vec<Point>, Vec<bool>) {
+        let (s1_normal, s2_normal) = canonicalize_join_normals(s1_normal, s2_normal);
+        let in_dot_out = -s1_normal.x * s2_normal.x + -s1_normal.y * s2_normal.y;
+        if 2. <= self.limit * self.limit * (1. - in_dot_out) {
+            let start = pivot + s1_normal * half_width;
+            let end = pivot + s2_normal * half_width;
+            if let Some(intersection) = line_intersection(start, s1_normal, end, s2_normal) {
+                // The two edges meeting at `pivot` coincide with the
+                // adjoining segment quads' cross-sections; the miter tip's
+                // two edges are the true outer boundary.
+                let points = vec![pivot + s1_normal * half_width, intersection, pivot + s2_normal * half_width, pivot];
+                return (points, vec![true, true, false, false]);
+            }
         }
+        bevel_points(pivot, half_width, s1_normal, s2_normal)
+    }
+    fn clone_box(&self) -> Box<dyn Joiner> {
+        Box::new(*self)
     }
 }
 
-fn bevel(
-    dest: &mut PathBuilder,
-    style: &StrokeStyle,
-    pt: Point,
-    s1_normal: Vector,
-    s2_normal: Vector,
-) {
-    let offset = style.width / 2.;
-    dest.tri(pt.x + s1_normal.x * offset, pt.y + s1_normal.y * offset,
-          pt.x + s2_normal.x * offset, pt.y + s2_normal.y * offset,
-          pt.x, pt.y);
+fn default_joiner(join: LineJoin, miter_limit: f32) -> Box<dyn Joiner> {
+    match join {
+        LineJoin::Round => Box::new(RoundJoin),
+        LineJoin::Miter => Box::new(MiterJoin { limit: miter_limit }),
+        LineJoin::Bevel => Box::new(BevelJoin),
+    }
 }
 
 /* given a normal rotate the vector 90 degrees to the right clockwise
@@ -365,45 +587,572 @@ fn is_interior_angle(a: Vector, b: Vector) -> bool {
     dot(perp(a), b) > 0. || a == b /* 0 degrees is interior */
 }
 
+/// Normalizes a join's pair of segment normals so a `Joiner` always sees
+/// `s1_normal`/`s2_normal` on the convex (exterior) side of the join.
+fn canonicalize_join_normals(s1_normal: Vector, s2_normal: Vector) -> (Vector, Vector) {
+    if is_interior_angle(s1_normal, s2_normal) {
+        (flip(s2_normal), flip(s1_normal))
+    } else {
+        (s1_normal, s2_normal)
+    }
+}
+
+// XXX: joining uses `pt` which can cause seams because it lies halfway on a line and the
+// rasterizer may not find exactly the same spot
 fn join_line(
     dest: &mut PathBuilder,
     style: &StrokeStyle,
     pt: Point,
-    mut s1_normal: Vector,
-    mut s2_normal: Vector,
+    s1_normal: Vector,
+    s2_normal: Vector,
 ) {
-    if is_interior_angle(s1_normal, s2_normal) {
-        s2_normal = flip(s2_normal);
-        s1_normal = flip(s1_normal);
-        std::mem::swap(&mut s1_normal, &mut s2_normal);
+    let half_width = style.width / 2.;
+    match &style.joiner {
+        Some(joiner) => joiner.join(dest, pt, half_width, s1_normal, s2_normal),
+        None => default_joiner(style.join, style.miter_limit).join(dest, pt, half_width, s1_normal, s2_normal),
+    }
+}
+
+/// Like `PathOp`, but curves have already been flattened to `LineTo`s. The
+/// final `LineTo` of a flattened curve carries the curve's true tangent at
+/// that point, so the following join can use it instead of re-deriving an
+/// approximate normal from the chord of the last flattened segment.
+#[derive(Clone, Copy, Debug)]
+enum FlatPathOp {
+    MoveTo(Point),
+    LineTo(Point),
+    LineToWithTangent(Point, Vector),
+    Close,
+}
+
+fn to_gp_point(p: Point) -> GpPointR {
+    GpPointR { x: p.x as f64, y: p.y as f64 }
+}
+
+/// Computes the normal of a tangent vector using the same convention as
+/// `compute_normal`, i.e. perpendicular to the tangent's *unit* vector.
+fn compute_tangent_normal(tangent: &GpPointR) -> Option<Vector> {
+    let ux = tangent.x as f32;
+    let uy = tangent.y as f32;
+    let ulen = ux.hypot(uy);
+    if ulen == 0. {
+        return None;
     }
+    Some(Vector::new(-uy / ulen, ux / ulen))
+}
 
-    // XXX: joining uses `pt` which can cause seams because it lies halfway on a line and the
-    // rasterizer may not find exactly the same spot
-    let offset = style.width / 2.;
-    match style.join {
-        LineJoin::Round => {
-            dest.arc_wedge(pt, offset, s1_normal, s2_normal);
+/// Replaces every `QuadTo`/`CubicTo` in `path` with the `LineTo`s produced by
+/// flattening it through `CBezierFlattener`, so the stroker only ever has to
+/// deal with straight segments.
+fn flatten_path(path: &Path, tolerance: f32) -> Vec<FlatPathOp> {
+    struct FlattenSink {
+        ops: Vec<FlatPathOp>,
+    }
+    impl CFlatteningSink for FlattenSink {
+        fn AcceptPointAndTangent(&mut self, pt: &GpPointR, vec: &GpPointR, is_last: bool) -> HRESULT {
+            let p = Point::new(pt.x as f32, pt.y as f32);
+            match compute_tangent_normal(vec) {
+                Some(normal) if is_last => self.ops.push(FlatPathOp::LineToWithTangent(p, normal)),
+                _ => self.ops.push(FlatPathOp::LineTo(p)),
+            }
+            S_OK
         }
-        LineJoin::Miter => {
-            let in_dot_out = -s1_normal.x * s2_normal.x + -s1_normal.y * s2_normal.y;
-            if 2. <= style.miter_limit * style.miter_limit * (1. - in_dot_out) {
-                let start = pt + s1_normal * offset;
-                let end = pt + s2_normal * offset;
-                if let Some(intersection) = line_intersection(start, s1_normal, end, s2_normal) {
-                    // We won't have an intersection if the segments are parallel
-                    dest.quad(pt.x + s1_normal.x * offset, pt.y + s1_normal.y * offset,
-                    intersection.x, intersection.y,
-                    pt.x + s2_normal.x * offset, pt.y + s2_normal.y * offset,
-                    pt.x, pt.y);
+
+        fn AcceptPoint(&mut self, _pt: &GpPointR, _t: f64, _aborted: &mut bool) -> HRESULT {
+            S_OK
+        }
+    }
+
+    let mut flat_ops = Vec::with_capacity(path.ops.len());
+    let mut cur_pt = Point::zero();
+    let mut start_pt = Point::zero();
+    for op in &path.ops {
+        match *op {
+            PathOp::MoveTo(pt) => {
+                flat_ops.push(FlatPathOp::MoveTo(pt));
+                cur_pt = pt;
+                start_pt = pt;
+            }
+            PathOp::LineTo(pt) => {
+                flat_ops.push(FlatPathOp::LineTo(pt));
+                cur_pt = pt;
+            }
+            PathOp::QuadTo(cp, pt) => {
+                // Elevate the quadratic to an equivalent cubic so we can
+                // flatten it with the same `CBezierFlattener` used for
+                // `CubicTo`.
+                let c1 = cur_pt + (cp - cur_pt) * (2. / 3.);
+                let c2 = pt + (cp - pt) * (2. / 3.);
+                let bezier = CBezier::new([to_gp_point(cur_pt), to_gp_point(c1), to_gp_point(c2), to_gp_point(pt)]);
+                let mut sink = FlattenSink { ops: Vec::new() };
+                CBezierFlattener::new(&bezier, &mut sink, tolerance as f64).Flatten(true);
+                flat_ops.extend(sink.ops);
+                cur_pt = pt;
+            }
+            PathOp::CubicTo(c1, c2, pt) => {
+                let bezier = CBezier::new([to_gp_point(cur_pt), to_gp_point(c1), to_gp_point(c2), to_gp_point(pt)]);
+                let mut sink = FlattenSink { ops: Vec::new() };
+                CBezierFlattener::new(&bezier, &mut sink, tolerance as f64).Flatten(true);
+                flat_ops.extend(sink.ops);
+                cur_pt = pt;
+            }
+            PathOp::Close => {
+                flat_ops.push(FlatPathOp::Close);
+                cur_pt = start_pt;
+            }
+        }
+    }
+    flat_ops
+}
+
+#[cfg(test)]
+mod curve_flattening_tests {
+    use super::*;
+
+    #[test]
+    fn flatten_path_end_tangent_matches_curve_not_chord() {
+        // A cubic whose true end tangent (proportional to p3 - c2) points
+        // nearly straight along +x, while its chord (p0 -> p3) points
+        // diagonally - so a chord-derived normal and the curve's true
+        // tangent normal are clearly distinguishable.
+        let p0 = Point::new(0., 0.);
+        let c1 = Point::new(0., 50.);
+        let c2 = Point::new(50., 100.);
+        let p3 = Point::new(100., 100.);
+
+        let mut pb = PathBuilder::new();
+        pb.move_to(p0.x, p0.y);
+        pb.cubic_to(c1.x, c1.y, c2.x, c2.y, p3.x, p3.y);
+        let path = pb.finish();
+
+        let flat = flatten_path(&path, 0.01);
+        let tangent_normal = match flat.last() {
+            Some(FlatPathOp::LineToWithTangent(_, normal)) => *normal,
+            other => panic!("expected the curve's final point to carry its tangent, got {:?}", other),
+        };
+
+        let true_tangent_normal = normal_from_tangent(bezier_tangent_at(&[p0, c1, c2, p3], 1.0)).unwrap();
+        let chord_normal = compute_normal(p0, p3).unwrap();
+
+        assert!(
+            dot(tangent_normal, true_tangent_normal) > 0.999,
+            "carried tangent {:?} should match the curve's true end tangent {:?}",
+            tangent_normal,
+            true_tangent_normal
+        );
+        assert!(
+            dot(tangent_normal, chord_normal) < 0.95,
+            "carried tangent {:?} should not just be the chord's normal {:?}",
+            tangent_normal,
+            chord_normal
+        );
+    }
+
+    #[test]
+    fn flatten_path_respects_tolerance() {
+        let mut pb = PathBuilder::new();
+        pb.move_to(0., 0.);
+        pb.cubic_to(0., 100., 100., 100., 100., 0.);
+        let path = pb.finish();
+
+        let loose = flatten_path(&path, 10.);
+        let tight = flatten_path(&path, 0.01);
+        assert!(
+            tight.len() > loose.len(),
+            "a tighter tolerance ({} ops) should flatten to more segments than a loose one ({} ops)",
+            tight.len(),
+            loose.len()
+        );
+    }
+
+    #[test]
+    fn stroke_to_path_flattens_curves_without_panicking() {
+        // The headline case this flattener exists for: a path containing
+        // QuadTo/CubicTo must stroke without panicking, and with the default
+        // butt caps and miter joins the stroked output (built purely from
+        // PathBuilder::quad/tri calls) should never itself contain a curve
+        // op. Round caps or joins would add `arc_wedge`'s cubics.
+        let mut pb = PathBuilder::new();
+        pb.move_to(0., 0.);
+        pb.quad_to(50., 100., 100., 0.);
+        pb.cubic_to(120., 50., 180., -50., 200., 0.);
+        pb.line_to(250., 0.);
+        let path = pb.finish();
+
+        let stroked = stroke_to_path(&path, &StrokeStyle::default());
+        assert!(!stroked.ops.is_empty());
+        assert!(
+            stroked.ops.iter().all(|op| !matches!(op, PathOp::QuadTo(..) | PathOp::CubicTo(..))),
+            "butt/miter stroked output should never contain a curve op"
+        );
+    }
+}
+
+/// Splits `ops` into dashes according to `dash_array`/`dash_offset`, following
+/// the SVG/PostScript convention: an odd-length array is treated as if it
+/// were doubled, and the phase starts `dash_offset` units into the pattern.
+/// Each "on" interval becomes its own open subpath so the existing cap logic
+/// produces end caps on every dash, and "off" intervals simply emit nothing,
+/// breaking the subpath so no join is generated across the gap. Closed
+/// subpaths dash continuously through the implicit closing segment rather
+/// than restarting the phase there.
+fn dash_path(ops: &[FlatPathOp], dash_array: &[f32], dash_offset: f32) -> Vec<FlatPathOp> {
+    let mut pattern = dash_array.to_vec();
+    if pattern.len() % 2 == 1 {
+        let doubled = pattern.clone();
+        pattern.extend(doubled);
+    }
+    let total: f32 = pattern.iter().sum();
+    if pattern.is_empty() || total <= 0. {
+        return ops.to_vec();
+    }
+
+    let mut out = Vec::with_capacity(ops.len());
+    let mut i = 0;
+    let mut last_start = None;
+    while i < ops.len() {
+        match ops[i] {
+            FlatPathOp::MoveTo(_) | FlatPathOp::LineTo(_) | FlatPathOp::LineToWithTangent(..) => {
+                // A segment right after a `Close` continues from the closed
+                // subpath's start; one with no current point at all only
+                // sets it.
+                let (start, mut j) = match (ops[i], last_start) {
+                    (FlatPathOp::LineTo(_) | FlatPathOp::LineToWithTangent(..), Some(start)) => (start, i),
+                    (FlatPathOp::MoveTo(pt) | FlatPathOp::LineTo(pt) | FlatPathOp::LineToWithTangent(pt, _), _) => (pt, i + 1),
+                    (FlatPathOp::Close, _) => unreachable!(),
+                };
+                last_start = Some(start);
+                let mut points = vec![start];
+                let mut tangents = Vec::new();
+                let mut closed = false;
+                while j < ops.len() {
+                    match ops[j] {
+                        FlatPathOp::LineTo(pt) => {
+                            points.push(pt);
+                            tangents.push(None);
+                        }
+                        FlatPathOp::LineToWithTangent(pt, tangent) => {
+                            points.push(pt);
+                            tangents.push(Some(tangent));
+                        }
+                        FlatPathOp::Close => {
+                            closed = true;
+                            j += 1;
+                            break;
+                        }
+                        FlatPathOp::MoveTo(_) => break,
+                    }
+                    j += 1;
+                }
+                if closed {
+                    // Dash through the implicit segment back to the start
+                    // point, rather than treating the subpath as ending here.
+                    points.push(start);
+                    tangents.push(None);
                 }
+                dash_subpath(&points, &tangents, &pattern, total, dash_offset, &mut out);
+                i = j;
+            }
+            // A `Close` with no subpath open has nothing to close.
+            FlatPathOp::Close => i += 1,
+        }
+    }
+    out
+}
+
+/// Walks a single subpath's vertices, cycling through `pattern` starting
+/// `dash_offset` units in, emitting a `MoveTo` at the start of each "on" run
+/// and `LineTo`s (preserving any tangent override) through to its end.
+fn dash_subpath(
+    points: &[Point],
+    tangents: &[Option<Vector>],
+    pattern: &[f32],
+    total: f32,
+    dash_offset: f32,
+    out: &mut Vec<FlatPathOp>,
+) {
+    let mut offset = dash_offset % total;
+    if offset < 0. {
+        offset += total;
+    }
+    let mut idx = 0;
+    while offset >= pattern[idx] {
+        offset -= pattern[idx];
+        idx = (idx + 1) % pattern.len();
+    }
+    let mut remaining = pattern[idx] - offset;
+    let mut on = idx % 2 == 0;
+
+    if on {
+        out.push(FlatPathOp::MoveTo(points[0]));
+    }
+
+    for i in 0..points.len().saturating_sub(1) {
+        let seg_start = points[i];
+        let seg_end = points[i + 1];
+        let seg_tangent = tangents[i];
+        let dir = seg_end - seg_start;
+        let seg_len = dir.length();
+        let unit = if seg_len > 0. { dir / seg_len } else { Vector::zero() };
+
+        let mut pos = seg_start;
+        let mut seg_remaining = seg_len;
+        loop {
+            if seg_remaining <= remaining {
+                remaining -= seg_remaining;
+                if on {
+                    match seg_tangent {
+                        Some(t) => out.push(FlatPathOp::LineToWithTangent(seg_end, t)),
+                        None => out.push(FlatPathOp::LineTo(seg_end)),
+                    }
+                }
+                break;
             } else {
-                bevel(dest, style, pt, s1_normal, s2_normal);
+                let boundary = pos + unit * remaining;
+                if on {
+                    out.push(FlatPathOp::LineTo(boundary));
+                } else {
+                    out.push(FlatPathOp::MoveTo(boundary));
+                }
+                seg_remaining -= remaining;
+                pos = boundary;
+                on = !on;
+                idx = (idx + 1) % pattern.len();
+                remaining = pattern[idx];
             }
         }
-        LineJoin::Bevel => {
-            bevel(dest, style, pt, s1_normal, s2_normal);
+    }
+}
+
+#[cfg(test)]
+mod dash_tests {
+    use super::*;
+
+    fn line(points: &[(f32, f32)]) -> Vec<FlatPathOp> {
+        let mut ops = vec![FlatPathOp::MoveTo(Point::new(points[0].0, points[0].1))];
+        for &(x, y) in &points[1..] {
+            ops.push(FlatPathOp::LineTo(Point::new(x, y)));
         }
+        ops
+    }
+
+    fn move_to_points(ops: &[FlatPathOp]) -> Vec<(f32, f32)> {
+        ops.iter()
+            .filter_map(|op| match *op {
+                FlatPathOp::MoveTo(p) => Some((p.x, p.y)),
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn line_to_points(ops: &[FlatPathOp]) -> Vec<(f32, f32)> {
+        ops.iter()
+            .filter_map(|op| match *op {
+                FlatPathOp::LineTo(p) => Some((p.x, p.y)),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn odd_length_array_is_doubled() {
+        // A 100-unit line with pattern [10] (odd length) should dash exactly
+        // as if the pattern had been given as [10, 10].
+        let ops = line(&[(0., 0.), (100., 0.)]);
+        let odd = dash_path(&ops, &[10.], 0.);
+        let doubled = dash_path(&ops, &[10., 10.], 0.);
+        assert_eq!(move_to_points(&odd), move_to_points(&doubled));
+        assert_eq!(line_to_points(&odd), line_to_points(&doubled));
+        // 5 "on" dashes of length 10, starting at x = 0, 20, 40, 60, 80.
+        assert_eq!(move_to_points(&odd), vec![(0., 0.), (20., 0.), (40., 0.), (60., 0.), (80., 0.)]);
+    }
+
+    #[test]
+    fn dash_offset_shifts_and_wraps_the_phase() {
+        let ops = line(&[(0., 0.), (100., 0.)]);
+        // pattern [10, 10], offset 10 starts one full "on" into the cycle,
+        // i.e. as if the line itself started 10 units later.
+        let shifted = dash_path(&ops, &[10., 10.], 10.);
+        assert_eq!(move_to_points(&shifted), vec![(10., 0.), (30., 0.), (50., 0.), (70., 0.), (90., 0.)]);
+
+        // A negative offset, or one larger than the pattern's total period,
+        // must wrap around to the same phase as its value modulo the total.
+        let negative = dash_path(&ops, &[10., 10.], -10.);
+        let wrapped_positive = dash_path(&ops, &[10., 10.], 10.);
+        assert_eq!(move_to_points(&negative), move_to_points(&wrapped_positive));
+
+        let over_period = dash_path(&ops, &[10., 10.], 30.);
+        assert_eq!(move_to_points(&over_period), move_to_points(&wrapped_positive));
+    }
+
+    #[test]
+    fn closed_subpath_dashes_continuously_through_closing_segment() {
+        // A closed 40-unit square (10 units per side) with a 10-on/10-off
+        // pattern: since each side is exactly one "on" or "off" interval
+        // (half of the 20-unit period), continuous
+        // phasing through the implicit closing segment means every side
+        // alternates on/off rather than each side always starting "on".
+        let mut ops = line(&[(0., 0.), (10., 0.), (10., 10.), (0., 10.)]);
+        ops.push(FlatPathOp::Close);
+        let dashed = dash_path(&ops, &[10., 10.], 0.);
+        assert_eq!(
+            move_to_points(&dashed),
+            vec![(0., 0.), (10., 10.)],
+            "only every other side should start a dash if phase carries through the close"
+        );
+    }
+}
+
+/// Strokes the segment from `cur_pt` to `pt`, joining with `last_normal` if
+/// this isn't the first segment of the subpath, and returns the segment's
+/// chord normal so the caller can feed it into the next join (or override it
+/// with a curve's true tangent, see `FlatPathOp::LineToWithTangent`).
+fn stroke_line_segment(
+    dest: &mut PathBuilder,
+    style: &StrokeStyle,
+    cur_pt: Point,
+    pt: Point,
+    last_normal: Vector,
+    start_point: &mut Option<(Point, Vector)>,
+) -> Option<Vector> {
+    let normal = compute_normal(cur_pt, pt)?;
+    if start_point.is_none() {
+        *start_point = Some((cur_pt, normal));
+    } else {
+        join_line(dest, style, cur_pt, last_normal, normal);
+    }
+
+    let half_width = style.width / 2.;
+    dest.quad(
+        cur_pt.x + normal.x * half_width,
+        cur_pt.y + normal.y * half_width,
+        pt.x + normal.x * half_width, pt.y + normal.y * half_width,
+        pt.x + -normal.x * half_width, pt.y + -normal.y * half_width,
+        cur_pt.x - normal.x * half_width,
+        cur_pt.y - normal.y * half_width,
+    );
+
+    Some(normal)
+}
+
+/// An op that `stroke_ops` can walk: either a subpath boundary (`MoveTo`/
+/// `Close`, handled generically) or a segment advancing the subpath to
+/// `end_point()`, handed off to the caller's segment-stroking callback.
+/// Implemented by `FlatPathOp` (straight, already-flattened segments, used
+/// by `stroke_to_path`) and `PathOp` (segments that may still be curves,
+/// used by `stroke_to_path_with_offset_curves`).
+trait WalkOp: Copy {
+    fn as_move_to(&self) -> Option<Point>;
+    fn is_close(&self) -> bool;
+    /// The point this segment advances the current point to. Never called
+    /// for an op `as_move_to`/`is_close` report as a boundary.
+    fn end_point(&self) -> Point;
+}
+
+impl WalkOp for FlatPathOp {
+    fn as_move_to(&self) -> Option<Point> {
+        match *self {
+            FlatPathOp::MoveTo(pt) => Some(pt),
+            _ => None,
+        }
+    }
+    fn is_close(&self) -> bool {
+        matches!(self, FlatPathOp::Close)
+    }
+    fn end_point(&self) -> Point {
+        match *self {
+            FlatPathOp::LineTo(pt) | FlatPathOp::LineToWithTangent(pt, _) => pt,
+            FlatPathOp::MoveTo(_) | FlatPathOp::Close => unreachable!(),
+        }
+    }
+}
+
+impl WalkOp for PathOp {
+    fn as_move_to(&self) -> Option<Point> {
+        match *self {
+            PathOp::MoveTo(pt) => Some(pt),
+            _ => None,
+        }
+    }
+    fn is_close(&self) -> bool {
+        matches!(self, PathOp::Close)
+    }
+    fn end_point(&self) -> Point {
+        match *self {
+            PathOp::LineTo(pt) | PathOp::QuadTo(_, pt) | PathOp::CubicTo(_, _, pt) => pt,
+            PathOp::MoveTo(_) | PathOp::Close => unreachable!(),
+        }
+    }
+}
+
+/// Shared by `stroke_to_path` and `stroke_to_path_with_offset_curves`: walks
+/// `ops`, capping each subpath's two ends, joining consecutive segments
+/// (including the implicit segment a `Close` adds back to the subpath's
+/// start), and tracking `last_normal`/the subpath's start point/normal along
+/// the way. The two callers differ only in how an individual segment is
+/// stroked, which they supply via `stroke_segment`: given the segment's
+/// start point and the op describing it, it should emit the segment's
+/// geometry into `dest` and return the normal to join with next (`None` if
+/// the segment was degenerate and nothing was emitted).
+fn stroke_ops<Op: WalkOp>(
+    dest: &mut PathBuilder,
+    style: &StrokeStyle,
+    ops: &[Op],
+    mut stroke_segment: impl FnMut(&mut PathBuilder, Point, &Op, Vector, &mut Option<(Point, Vector)>) -> Option<Vector>,
+) {
+    let mut cur_pt = None;
+    let mut last_normal = Vector::zero();
+    let half_width = style.width / 2.;
+    let mut start_point = None;
+    for op in ops {
+        if let Some(pt) = op.as_move_to() {
+            if let (Some(cur_pt), Some((point, normal))) = (cur_pt, start_point) {
+                // cap end
+                cap_line(dest, style, cur_pt, last_normal);
+                // cap beginning
+                cap_line(dest, style, point, flip(normal));
+            }
+            start_point = None;
+            cur_pt = Some(pt);
+        } else if op.is_close() {
+            if let (Some(cur_pt), Some((end_point, start_normal))) = (cur_pt, start_point) {
+                if let Some(normal) = compute_normal(cur_pt, end_point) {
+                    join_line(dest, style, cur_pt, last_normal, normal);
+
+                    dest.quad(
+                        cur_pt.x + normal.x * half_width,
+                        cur_pt.y + normal.y * half_width,
+                        end_point.x + normal.x * half_width,
+                        end_point.y + normal.y * half_width,
+                        end_point.x + -normal.x * half_width,
+                        end_point.y + -normal.y * half_width,
+                        cur_pt.x - normal.x * half_width,
+                        cur_pt.y - normal.y * half_width,
+                    );
+                    join_line(dest, style, end_point, normal, start_normal);
+                } else {
+                    join_line(dest, style, end_point, last_normal, start_normal);
+                }
+            }
+            cur_pt = start_point.map(|x| x.0);
+            start_point = None;
+        } else {
+            let end = op.end_point();
+            if cur_pt.is_none() {
+                start_point = None;
+            } else if let Some(cur) = cur_pt {
+                if let Some(normal) = stroke_segment(dest, cur, op, last_normal, &mut start_point) {
+                    last_normal = normal;
+                }
+            }
+            cur_pt = Some(end);
+        }
+    }
+    if let (Some(cur_pt), Some((point, normal))) = (cur_pt, start_point) {
+        // cap end
+        cap_line(dest, style, cur_pt, last_normal);
+        // cap beginning
+        cap_line(dest, style, point, flip(normal));
     }
 }
 
@@ -414,82 +1163,652 @@ pub fn stroke_to_path(path: &Path, style: &StrokeStyle) -> Path {
         return stroked_path.finish();
     }
 
+    let flat_ops = flatten_path(path, style.flatten_tolerance);
+    let flat_ops = if style.dash_array.is_empty() {
+        flat_ops
+    } else {
+        dash_path(&flat_ops, &style.dash_array, style.dash_offset)
+    };
+
+    stroke_ops(&mut stroked_path, style, &flat_ops, |dest, cur, op, last_normal, start_point| {
+        match *op {
+            FlatPathOp::LineTo(pt) => stroke_line_segment(dest, style, cur, pt, last_normal, start_point),
+            FlatPathOp::LineToWithTangent(pt, tangent_normal) => {
+                // Use the curve's true tangent, rather than this segment's
+                // chord, for the join that follows.
+                stroke_line_segment(dest, style, cur, pt, last_normal, start_point).map(|_| tangent_normal)
+            }
+            FlatPathOp::MoveTo(_) | FlatPathOp::Close => unreachable!(),
+        }
+    });
+
+    stroked_path.finish()
+}
+
+/// A vertex of a triangle-mesh representation of a stroked path, carrying a
+/// per-vertex alpha coverage so the mesh can be uploaded to a GPU and drawn
+/// with a trivial coverage-as-alpha shader.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Vertex {
+    pub x: f32,
+    pub y: f32,
+    pub coverage: f32,
+}
+
+impl Vertex {
+    fn new(p: Point, coverage: f32) -> Vertex {
+        Vertex { x: p.x, y: p.y, coverage }
+    }
+}
+
+/// Roughly half a device pixel, the width of the AA fringe outset from each
+/// stroked primitive's true boundary.
+const AA_FEATHER_DEVICE_PX: f32 = 0.5;
+
+/// Collects the triangles making up a `stroke_to_aa_vertices` mesh.
+struct AaMeshBuilder {
+    vertices: Vec<Vertex>,
+}
+
+impl AaMeshBuilder {
+    fn new() -> AaMeshBuilder {
+        AaMeshBuilder { vertices: Vec::new() }
+    }
+
+    fn tri(&mut self, a: Point, ca: f32, b: Point, cb: f32, c: Point, cc: f32) {
+        self.vertices.push(Vertex::new(a, ca));
+        self.vertices.push(Vertex::new(b, cb));
+        self.vertices.push(Vertex::new(c, cc));
+    }
+
+    /// Transforms every vertex into device space with `transform`, sized for
+    /// by the caller's choice of feather (see `stroke_to_aa_vertices`).
+    fn finish(self, transform: &Transform) -> Vec<Vertex> {
+        self.vertices
+            .into_iter()
+            .map(|v| {
+                let p = transform.transform_point(Point::new(v.x, v.y));
+                Vertex { x: p.x, y: p.y, coverage: v.coverage }
+            })
+            .collect()
+    }
+}
+
+/// The length, in local (pre-`transform`) space, that a unit vector pointing
+/// along `direction` must have to cover `AA_FEATHER_DEVICE_PX` once mapped
+/// into device space by `transform`'s linear part (translation doesn't
+/// affect vector lengths, so it's ignored). Unlike a single scale factor
+/// derived from `transform`'s determinant, this varies with `direction`, so
+/// a skewed or anisotropically-scaled `transform` (e.g. differing x/y DPI)
+/// still gets a uniform ~half-device-pixel fringe in every direction rather
+/// than one that's too wide along one axis and too narrow along the other.
+fn feather_length(transform: &Transform, direction: Vector) -> f32 {
+    let device_direction = Vector::new(
+        direction.x * transform.m11 + direction.y * transform.m21,
+        direction.x * transform.m12 + direction.y * transform.m22,
+    );
+    let device_len = device_direction.length();
+    if device_len > 0. { AA_FEATHER_DEVICE_PX / device_len } else { AA_FEATHER_DEVICE_PX }
+}
+
+/// Appends `points` (the vertices, in order, of a convex polygon such as a
+/// stroked quad, join, or cap) as coverage-1.0 interior triangles, plus, for
+/// every edge marked `true` in `exterior`, a fringe of two triangles ramping
+/// from coverage 1.0 at the true edge to 0.0 at an outset copy of that edge
+/// pushed outward along its normal by `feather_length(transform, normal)`, so
+/// the fringe stays a uniform width in device space even under a skewed or
+/// anisotropically-scaled `transform`.
+///
+/// `exterior[i]` corresponds to the edge `points[i] -> points[(i + 1) %
+/// points.len()]`. Edges that are `false` lie on a seam shared exactly with
+/// a neighboring primitive (e.g. a join's radii, or a segment quad's
+/// cross-section where a cap or join continues) and are left unfeathered,
+/// since both primitives meeting there are already coverage-1.0 right up to
+/// the shared edge; feathering either side would double-blend the seam.
+fn feathered_polygon(dest: &mut AaMeshBuilder, points: &[Point], exterior: &[bool], transform: &Transform) {
+    if points.len() < 3 {
+        return;
+    }
+
+    for i in 1..points.len() - 1 {
+        dest.tri(points[0], 1., points[i], 1., points[i + 1], 1.);
+    }
+
+    let sum = points.iter().fold(Vector::zero(), |acc, p| acc + Vector::new(p.x, p.y));
+    let centroid = Point::zero() + sum / points.len() as f32;
+
+    let n = points.len();
+    for i in 0..n {
+        if !exterior[i] {
+            continue;
+        }
+        let a = points[i];
+        let b = points[(i + 1) % n];
+        if let Some(normal) = compute_normal(a, b) {
+            let mid = a + (b - a) * 0.5;
+            let outward = if dot(normal, mid - centroid) >= 0. { normal } else { flip(normal) };
+            let feather = feather_length(transform, outward);
+            let a_out = a + outward * feather;
+            let b_out = b + outward * feather;
+            dest.tri(a, 1., b, 1., b_out, 0.);
+            dest.tri(a, 1., b_out, 0., a_out, 0.);
+        }
+    }
+}
+
+fn arc_wedge_points(center: Point, radius: f32, a: Vector, b: Vector) -> Vec<Point> {
+    // Matches the pie-slice shape built by `PathBuilder::arc_wedge`, but
+    // tessellated directly into a polygon instead of a cubic approximation.
+    const STEPS: usize = 16;
+    let angle_a = a.y.atan2(a.x);
+    let angle_b = b.y.atan2(b.x);
+    // Sweep the short way around, as `bisect`/`arc` assume an angle <= pi
+    // between `a` and `b`.
+    let mut delta = angle_b - angle_a;
+    if delta > std::f32::consts::PI {
+        delta -= 2. * std::f32::consts::PI;
+    } else if delta < -std::f32::consts::PI {
+        delta += 2. * std::f32::consts::PI;
+    }
+    // When `a` and `b` are antiparallel (a round cap), both ways around are a
+    // half turn; go through `bisect(a, b)`, the side `arc` takes.
+    if dot(a, b) < 0. {
+        let ccw = dot(perp(a), bisect(a, b)) > 0.;
+        if ccw && delta < 0. {
+            delta += 2. * std::f32::consts::PI;
+        } else if !ccw && delta > 0. {
+            delta -= 2. * std::f32::consts::PI;
+        }
+    }
+
+    let mut points = Vec::with_capacity(STEPS + 2);
+    for i in 0..=STEPS {
+        let t = i as f32 / STEPS as f32;
+        let angle = angle_a + delta * t;
+        points.push(Point::new(center.x + angle.cos() * radius, center.y + angle.sin() * radius));
+    }
+    points.push(center);
+    points
+}
+
+/// The exterior-edge mask for a polygon built from `arc_wedge_points`: every
+/// edge along the arc is on the stroke's true outer boundary, but the last
+/// two edges (the two "radius" legs back to `center`) coincide with the
+/// neighboring primitive's cross-section and must not be feathered.
+fn arc_wedge_exterior_mask(len: usize) -> Vec<bool> {
+    (0..len).map(|i| i < len.saturating_sub(2)).collect()
+}
+
+/// Like `cap_line`, but returns the cap decoration as a point list instead
+/// of emitting it into a `PathBuilder`, routing through `style.capper` (or
+/// the builtin decoration for `style.cap`) exactly as `cap_line` does.
+fn cap_points(style: &StrokeStyle, pt: Point, normal: Vector) -> (Vec<Point>, Vec<bool>) {
+    let half_width = style.width / 2.;
+    match &style.capper {
+        Some(capper) => capper.cap_points(pt, half_width, normal),
+        None => default_capper(style.cap).cap_points(pt, half_width, normal),
+    }
+}
+
+/// Like `join_line`, but returns the join decoration as a point list instead
+/// of emitting it into a `PathBuilder`, routing through `style.joiner` (or
+/// the builtin decoration for `style.join`) exactly as `join_line` does.
+fn join_points(style: &StrokeStyle, pt: Point, s1_normal: Vector, s2_normal: Vector) -> (Vec<Point>, Vec<bool>) {
+    let half_width = style.width / 2.;
+    match &style.joiner {
+        Some(joiner) => joiner.join_points(pt, half_width, s1_normal, s2_normal),
+        None => default_joiner(style.join, style.miter_limit).join_points(pt, half_width, s1_normal, s2_normal),
+    }
+}
+
+/// The segment quad's rails (`cur_pt`/`pt` offset along `normal`) are the
+/// stroke's true outer boundary; its two cross-section edges, at `cur_pt`
+/// and at `pt`, coincide with whatever cap or join continues the stroke
+/// there and must not be feathered. At a butt-capped end nothing continues
+/// the stroke (`ButtCap` emits no cap polygon), and that edge is left
+/// unfeathered as well.
+fn segment_quad_points(cur_pt: Point, pt: Point, normal: Vector, half_width: f32) -> (Vec<Point>, Vec<bool>) {
+    let points = vec![
+        cur_pt + normal * half_width,
+        pt + normal * half_width,
+        pt - normal * half_width,
+        cur_pt - normal * half_width,
+    ];
+    (points, vec![true, false, true, false])
+}
+
+/// Like `stroke_to_path`, but instead of an opaque hard-edged `Path`,
+/// produces a triangle mesh with per-vertex alpha coverage: 1.0 across the
+/// interior of every stroked quad/join/cap, ramping to 0.0 over a roughly
+/// half-device-pixel fringe outset from the true stroke boundary (butt-capped
+/// ends get no fringe). The mesh is built in `path`'s space, with the fringe
+/// sized through `transform` so it is half a device pixel wide, and is then
+/// mapped through `transform`: the returned vertices are in device space, and
+/// the stroke width is scaled by `transform` along with the path.
+pub fn stroke_to_aa_vertices(path: &Path, style: &StrokeStyle, transform: &Transform) -> Vec<Vertex> {
+    let mut mesh = AaMeshBuilder::new();
+
+    if style.width <= 0. {
+        return mesh.finish(transform);
+    }
+
+    let flat_ops = flatten_path(path, style.flatten_tolerance);
+    let flat_ops = if style.dash_array.is_empty() {
+        flat_ops
+    } else {
+        dash_path(&flat_ops, &style.dash_array, style.dash_offset)
+    };
+
+    let half_width = style.width / 2.;
     let mut cur_pt = None;
     let mut last_normal = Vector::zero();
-    let half_width = style.width / 2.;
     let mut start_point = None;
-    for op in &path.ops {
+    for op in &flat_ops {
         match *op {
-            PathOp::MoveTo(pt) => {
+            FlatPathOp::MoveTo(pt) => {
                 if let (Some(cur_pt), Some((point, normal))) = (cur_pt, start_point) {
-                    // cap end
-                    cap_line(&mut stroked_path, style, cur_pt, last_normal);
-                    // cap beginning
-                    cap_line(&mut stroked_path, style, point, flip(normal));
+                    let (points, mask) = cap_points(style, cur_pt, last_normal);
+                    feathered_polygon(&mut mesh, &points, &mask, transform);
+                    let (points, mask) = cap_points(style, point, flip(normal));
+                    feathered_polygon(&mut mesh, &points, &mask, transform);
                 }
                 start_point = None;
                 cur_pt = Some(pt);
             }
-            PathOp::LineTo(pt) => {
+            FlatPathOp::LineTo(pt) | FlatPathOp::LineToWithTangent(pt, _) => {
+                let tangent_override = match *op {
+                    FlatPathOp::LineToWithTangent(_, tangent) => Some(tangent),
+                    _ => None,
+                };
                 if cur_pt.is_none() {
                     start_point = None;
-                } else if let Some(cur_pt) = cur_pt {
-                    if let Some(normal) = compute_normal(cur_pt, pt) {
+                } else if let Some(cur) = cur_pt {
+                    if let Some(normal) = compute_normal(cur, pt) {
                         if start_point.is_none() {
-                            start_point = Some((cur_pt, normal));
+                            start_point = Some((cur, normal));
                         } else {
-                            join_line(&mut stroked_path, style, cur_pt, last_normal, normal);
+                            let (points, mask) = join_points(style, cur, last_normal, normal);
+                            feathered_polygon(&mut mesh, &points, &mask, transform);
                         }
-
-                        stroked_path.quad(
-                            cur_pt.x + normal.x * half_width,
-                            cur_pt.y + normal.y * half_width,
-                            pt.x + normal.x * half_width, pt.y + normal.y * half_width,
-                            pt.x + -normal.x * half_width, pt.y + -normal.y * half_width,
-                            cur_pt.x - normal.x * half_width,
-                            cur_pt.y - normal.y * half_width,
-                        );
-
-                        last_normal = normal;
-
+                        let (points, mask) = segment_quad_points(cur, pt, normal, half_width);
+                        feathered_polygon(&mut mesh, &points, &mask, transform);
+                        last_normal = tangent_override.unwrap_or(normal);
                     }
                 }
                 cur_pt = Some(pt);
-
             }
-            PathOp::Close => {
+            FlatPathOp::Close => {
                 if let (Some(cur_pt), Some((end_point, start_normal))) = (cur_pt, start_point) {
                     if let Some(normal) = compute_normal(cur_pt, end_point) {
-                        join_line(&mut stroked_path, style, cur_pt, last_normal, normal);
-
-                        stroked_path.quad(
-                            cur_pt.x + normal.x * half_width,
-                            cur_pt.y + normal.y * half_width,
-                            end_point.x + normal.x * half_width,
-                            end_point.y + normal.y * half_width,
-                            end_point.x + -normal.x * half_width,
-                            end_point.y + -normal.y * half_width,
-                            cur_pt.x - normal.x * half_width,
-                            cur_pt.y - normal.y * half_width,
-                        );
-                        join_line(&mut stroked_path, style, end_point, normal, start_normal);
+                        let (points, mask) = join_points(style, cur_pt, last_normal, normal);
+                        feathered_polygon(&mut mesh, &points, &mask, transform);
+                        let (points, mask) = segment_quad_points(cur_pt, end_point, normal, half_width);
+                        feathered_polygon(&mut mesh, &points, &mask, transform);
+                        let (points, mask) = join_points(style, end_point, normal, start_normal);
+                        feathered_polygon(&mut mesh, &points, &mask, transform);
                     } else {
-                        join_line(&mut stroked_path, style, end_point, last_normal, start_normal);
+                        let (points, mask) = join_points(style, end_point, last_normal, start_normal);
+                        feathered_polygon(&mut mesh, &points, &mask, transform);
                     }
                 }
                 cur_pt = start_point.map(|x| x.0);
                 start_point = None;
             }
-            PathOp::QuadTo(..) => panic!("Only flat paths handled"),
-            PathOp::CubicTo(..) => panic!("Only flat paths handled"),
         }
     }
     if let (Some(cur_pt), Some((point, normal))) = (cur_pt, start_point) {
-        // cap end
-        cap_line(&mut stroked_path, style, cur_pt, last_normal);
-        // cap beginning
-        cap_line(&mut stroked_path, style, point, flip(normal));
+        let (points, mask) = cap_points(style, cur_pt, last_normal);
+        feathered_polygon(&mut mesh, &points, &mask, transform);
+        let (points, mask) = cap_points(style, point, flip(normal));
+        feathered_polygon(&mut mesh, &points, &mask, transform);
+    }
+
+    mesh.finish(transform)
+}
+
+#[cfg(test)]
+mod stroke_to_aa_vertices_tests {
+    use super::*;
+
+    fn straight_line() -> Path {
+        let mut pb = PathBuilder::new();
+        pb.move_to(0., 0.);
+        pb.line_to(100., 0.);
+        pb.finish()
+    }
+
+    fn bent_line() -> Path {
+        let mut pb = PathBuilder::new();
+        pb.move_to(0., 0.);
+        pb.line_to(100., 0.);
+        pb.line_to(100., 100.);
+        pb.finish()
+    }
+
+    #[test]
+    fn custom_capper_changes_output() {
+        // The default `cap: LineCap::Butt` produces no cap geometry at all,
+        // so any vertices contributed by the line's two caps must have come
+        // from `style.capper`, not from `style.cap`. This is exactly the
+        // shape of bug that shipped once before: `cap_points` ignoring
+        // `style.capper` and falling back to `style.cap`'s builtin.
+        let path = straight_line();
+        let transform = Transform::identity();
+
+        let default_style = StrokeStyle { width: 10., ..StrokeStyle::default() };
+        let default_vertices = stroke_to_aa_vertices(&path, &default_style, &transform);
+
+        let overridden_style = StrokeStyle { width: 10., capper: Some(Box::new(RoundCap)), ..StrokeStyle::default() };
+        let overridden_vertices = stroke_to_aa_vertices(&path, &overridden_style, &transform);
+
+        assert!(
+            overridden_vertices.len() > default_vertices.len(),
+            "a custom capper should add cap geometry beyond the default butt cap's none: {} vs {}",
+            overridden_vertices.len(),
+            default_vertices.len()
+        );
+    }
+
+    #[test]
+    fn custom_joiner_changes_output() {
+        // `join: LineJoin::Miter` is the default; overriding `joiner` with a
+        // bevel should change the join's vertex count even though `join`
+        // itself was left untouched, proving `join_points` consults
+        // `style.joiner` rather than `style.join`.
+        let path = bent_line();
+        let transform = Transform::identity();
+
+        let default_style = StrokeStyle { width: 10., ..StrokeStyle::default() };
+        let default_vertices = stroke_to_aa_vertices(&path, &default_style, &transform);
+
+        let overridden_style = StrokeStyle { width: 10., joiner: Some(Box::new(BevelJoin)), ..StrokeStyle::default() };
+        let overridden_vertices = stroke_to_aa_vertices(&path, &overridden_style, &transform);
+
+        assert_ne!(
+            overridden_vertices.len(),
+            default_vertices.len(),
+            "a custom joiner should change the join's vertex count from the default miter join's"
+        );
+    }
+
+    #[test]
+    fn mesh_has_interior_coverage_one_and_fringe_coverage_zero() {
+        let path = straight_line();
+        let style = StrokeStyle { width: 10., ..StrokeStyle::default() };
+        let vertices = stroke_to_aa_vertices(&path, &style, &Transform::identity());
+
+        assert!(!vertices.is_empty());
+        assert!(
+            vertices.iter().any(|v| v.coverage == 1.),
+            "expected at least one full-coverage interior vertex"
+        );
+        assert!(
+            vertices.iter().any(|v| v.coverage == 0.),
+            "expected at least one zero-coverage outer fringe vertex"
+        );
+        assert!(
+            vertices.iter().all(|v| (0. ..=1.).contains(&v.coverage)),
+            "coverage should never fall outside [0, 1]"
+        );
+    }
+}
+
+fn bezier_point_at(p: &[Point; 4], t: f32) -> Point {
+    let u = 1. - t;
+    let w0 = u * u * u;
+    let w1 = 3. * u * u * t;
+    let w2 = 3. * u * t * t;
+    let w3 = t * t * t;
+    Point::new(
+        p[0].x * w0 + p[1].x * w1 + p[2].x * w2 + p[3].x * w3,
+        p[0].y * w0 + p[1].y * w1 + p[2].y * w2 + p[3].y * w3,
+    )
+}
+
+fn bezier_tangent_at(p: &[Point; 4], t: f32) -> Vector {
+    let u = 1. - t;
+    (p[1] - p[0]) * (3. * u * u) + (p[2] - p[1]) * (6. * u * t) + (p[3] - p[2]) * (3. * t * t)
+}
+
+fn mid_point(a: Point, b: Point) -> Point {
+    Point::new((a.x + b.x) / 2., (a.y + b.y) / 2.)
+}
+
+/// Splits a cubic bezier's control polygon at `t = 0.5` via de Casteljau's
+/// algorithm, mirroring `bezierflattener::CBezier::subdivide`.
+fn bezier_subdivide(p: &[Point; 4]) -> ([Point; 4], [Point; 4]) {
+    let p01 = mid_point(p[0], p[1]);
+    let p12 = mid_point(p[1], p[2]);
+    let p23 = mid_point(p[2], p[3]);
+    let p012 = mid_point(p01, p12);
+    let p123 = mid_point(p12, p23);
+    let p0123 = mid_point(p012, p123);
+    ([p[0], p01, p012, p0123], [p0123, p123, p23, p[3]])
+}
+
+/// Like `compute_normal`, but for a (non-unit) tangent vector rather than two
+/// points.
+fn normal_from_tangent(tangent: Vector) -> Option<Vector> {
+    let len = tangent.length();
+    if len == 0. {
+        return None;
+    }
+    Some(Vector::new(-tangent.y / len, tangent.x / len))
+}
+
+/// Offsets a cubic bezier's control polygon by `half_width` along its
+/// outward normal, using the Tiller-Hanson construction: each leg of the
+/// control polygon (`p0p1`, `p1p2`, `p2p3`) is pushed out along its own
+/// normal, and the offset curve's inner control points are the intersections
+/// of consecutive offset legs. Falls back to the (single) offset endpoint a
+/// leg shares with its neighbor when the two are too close to parallel for
+/// `line_intersection` to find one.
+fn tiller_hanson_offset(p: &[Point; 4], half_width: f32) -> [Point; 4] {
+    let n1_opt = compute_normal(p[1], p[2]);
+    // A zero-length leg has no normal of its own; borrow a neighbor's so a
+    // degenerate control point doesn't stall the whole offset.
+    let n0 = compute_normal(p[0], p[1]).or(n1_opt).unwrap_or_else(Vector::zero);
+    let n2 = compute_normal(p[2], p[3]).or(n1_opt).unwrap_or_else(Vector::zero);
+    let n1 = n1_opt.unwrap_or(n0);
+
+    let a0 = p[0] + n0 * half_width;
+    let b0 = p[1] + n0 * half_width;
+    let a1 = p[1] + n1 * half_width;
+    let b1 = p[2] + n1 * half_width;
+    let a2 = p[2] + n2 * half_width;
+    let b2 = p[3] + n2 * half_width;
+
+    let q1 = line_intersection(a0, n0, a1, n1).unwrap_or(b0);
+    let q2 = line_intersection(a1, n1, a2, n2).unwrap_or(b1);
+
+    [a0, q1, q2, b2]
+}
+
+/// A curve can't be subdivided more than this many times while offsetting,
+/// guarding against runaway recursion on a degenerate curve (mirrors
+/// `bezierflattener::MAX_DEPTH`).
+const MAX_OFFSET_DEPTH: u32 = 24;
+
+/// Appends the offset curve for `p` (outward by `half_width` along its
+/// normal) to `dest` as one or more `CubicTo`s, assuming `dest`'s current
+/// point is already `p[0]`'s offset. Subdivides `p` at `t = 0.5` until the
+/// Tiller-Hanson approximation's midpoint comes within `tolerance` of the
+/// curve's true offset at its midpoint.
+///
+/// Each half's Tiller-Hanson offset is re-derived independently after a
+/// subdivision, but the two halves still meet at the split point: their
+/// adjoining end legs (`p012 -> p0123` and `p0123 -> p123`) are collinear,
+/// so both offset the split point along the same normal. Only where the
+/// curve's tangent vanishes at the split (a cusp) do the halves borrow
+/// different neighboring normals, leaving a seam there.
+fn offset_cubic(dest: &mut PathBuilder, p: [Point; 4], half_width: f32, tolerance: f32, depth: u32) {
+    let q = tiller_hanson_offset(&p, half_width);
+
+    let within_tolerance = {
+        let approx_mid = bezier_point_at(&q, 0.5);
+        let true_mid = bezier_point_at(&p, 0.5);
+        let true_mid_offset = match normal_from_tangent(bezier_tangent_at(&p, 0.5)) {
+            Some(normal) => true_mid + normal * half_width,
+            None => true_mid,
+        };
+        (approx_mid - true_mid_offset).length() <= tolerance
+    };
+
+    if within_tolerance || depth >= MAX_OFFSET_DEPTH {
+        dest.cubic_to(q[1].x, q[1].y, q[2].x, q[2].y, q[3].x, q[3].y);
+    } else {
+        let (left, right) = bezier_subdivide(&p);
+        offset_cubic(dest, left, half_width, tolerance, depth + 1);
+        offset_cubic(dest, right, half_width, tolerance, depth + 1);
+    }
+}
+
+#[cfg(test)]
+mod offset_curve_tests {
+    use super::*;
+
+    fn count_cubics(path: &Path) -> usize {
+        path.ops.iter().filter(|op| matches!(op, PathOp::CubicTo(..))).count()
+    }
+
+    #[test]
+    fn parallel_legs_fall_back_to_the_shared_offset_endpoint() {
+        // A perfectly straight "curve": every leg of the control polygon is
+        // collinear, so every leg shares the same normal and
+        // `line_intersection` can't find one between consecutive legs.
+        let p = [
+            Point::new(0., 0.),
+            Point::new(10., 0.),
+            Point::new(20., 0.),
+            Point::new(30., 0.),
+        ];
+        let half_width = 2.5;
+        let q = tiller_hanson_offset(&p, half_width);
+        let normal = compute_normal(p[0], p[1]).unwrap();
+        // With every leg parallel, the Tiller-Hanson offset degenerates to a
+        // plain translation of the whole control polygon along the shared
+        // normal.
+        assert_eq!(q[0], p[0] + normal * half_width);
+        assert_eq!(q[1], p[1] + normal * half_width);
+        assert_eq!(q[2], p[2] + normal * half_width);
+        assert_eq!(q[3], p[3] + normal * half_width);
+    }
+
+    #[test]
+    fn tighter_tolerance_subdivides_more() {
+        // A curve with real curvature, so the Tiller-Hanson approximation's
+        // midpoint actually deviates from the true offset and tightening
+        // `tolerance` forces more subdivision.
+        let p = [
+            Point::new(0., 0.),
+            Point::new(0., 100.),
+            Point::new(100., 100.),
+            Point::new(100., 0.),
+        ];
+        let mut loose = PathBuilder::new();
+        loose.move_to(p[0].x, p[0].y);
+        offset_cubic(&mut loose, p, 10., 10., 0);
+        let loose_count = count_cubics(&loose.finish());
+
+        let mut tight = PathBuilder::new();
+        tight.move_to(p[0].x, p[0].y);
+        offset_cubic(&mut tight, p, 10., 0.01, 0);
+        let tight_count = count_cubics(&tight.finish());
+
+        assert!(
+            tight_count > loose_count,
+            "tight tolerance ({tight_count} cubics) should subdivide more than loose ({loose_count} cubics)"
+        );
+    }
+
+    #[test]
+    fn subdivision_stops_at_max_offset_depth_even_if_never_converged() {
+        // With `tolerance` of exactly 0, a curved control polygon's
+        // Tiller-Hanson approximation never satisfies `within_tolerance`
+        // (its deviation from the true offset is nonzero). Starting already
+        // at `MAX_OFFSET_DEPTH` must still emit a single cubic rather than
+        // recursing forever.
+        let p = [
+            Point::new(0., 0.),
+            Point::new(0., 100.),
+            Point::new(100., 100.),
+            Point::new(100., 0.),
+        ];
+        let mut dest = PathBuilder::new();
+        dest.move_to(p[0].x, p[0].y);
+        offset_cubic(&mut dest, p, 10., 0., MAX_OFFSET_DEPTH);
+        assert_eq!(count_cubics(&dest.finish()), 1);
+    }
+}
+
+/// Strokes the curve `p` (from `p[0]` to `p[3]`) by emitting its two offset
+/// curves (built by `offset_cubic`) as a single closed subpath, joining with
+/// `last_normal`/`start_point` exactly like `stroke_line_segment` does for a
+/// straight segment.
+fn stroke_cubic_with_offset_curves(
+    dest: &mut PathBuilder,
+    style: &StrokeStyle,
+    p: [Point; 4],
+    last_normal: Vector,
+    start_point: &mut Option<(Point, Vector)>,
+) -> Option<Vector> {
+    // A control point coinciding with its endpoint zeroes the end tangent;
+    // fall back to the direction of the nearest distinct control point, as
+    // `tiller_hanson_offset` does for a zero-length leg.
+    let start_normal = normal_from_tangent(bezier_tangent_at(&p, 0.))
+        .or_else(|| compute_normal(p[0], p[2]))
+        .or_else(|| compute_normal(p[0], p[3]))?;
+    let end_normal = normal_from_tangent(bezier_tangent_at(&p, 1.))
+        .or_else(|| compute_normal(p[1], p[3]))
+        .or_else(|| compute_normal(p[0], p[3]))?;
+
+    if start_point.is_none() {
+        *start_point = Some((p[0], start_normal));
+    } else {
+        join_line(dest, style, p[0], last_normal, start_normal);
     }
+
+    let half_width = style.width / 2.;
+    dest.move_to(p[0].x + start_normal.x * half_width, p[0].y + start_normal.y * half_width);
+    offset_cubic(dest, p, half_width, style.flatten_tolerance, 0);
+    dest.line_to(p[3].x - end_normal.x * half_width, p[3].y - end_normal.y * half_width);
+    offset_cubic(dest, [p[3], p[2], p[1], p[0]], half_width, style.flatten_tolerance, 0);
+    dest.close();
+
+    Some(end_normal)
+}
+
+/// Like `stroke_to_path`, but strokes `QuadTo`/`CubicTo` curves directly via
+/// analytic offset curves (the Tiller-Hanson construction, see
+/// `tiller_hanson_offset`) instead of first flattening them to polylines, so
+/// the output `Path` stays curved and uses far fewer points at high zoom.
+/// Straight `LineTo` segments are unaffected, since their offset is already
+/// exact without any curve fitting. Dashing isn't supported here: splitting
+/// dashes at even arc-length intervals needs the polyline `flatten_path`
+/// already produces for `stroke_to_path`, so a dashed style should use that
+/// entry point instead.
+pub fn stroke_to_path_with_offset_curves(path: &Path, style: &StrokeStyle) -> Path {
+    let mut stroked_path = PathBuilder::new();
+
+    if style.width <= 0. {
+        return stroked_path.finish();
+    }
+
+    stroke_ops(&mut stroked_path, style, &path.ops, |dest, cur, op, last_normal, start_point| {
+        match *op {
+            PathOp::LineTo(pt) => stroke_line_segment(dest, style, cur, pt, last_normal, start_point),
+            PathOp::QuadTo(cp, pt) => {
+                let c1 = cur + (cp - cur) * (2. / 3.);
+                let c2 = pt + (cp - pt) * (2. / 3.);
+                stroke_cubic_with_offset_curves(dest, style, [cur, c1, c2, pt], last_normal, start_point)
+            }
+            PathOp::CubicTo(c1, c2, pt) => {
+                stroke_cubic_with_offset_curves(dest, style, [cur, c1, c2, pt], last_normal, start_point)
+            }
+            PathOp::MoveTo(_) | PathOp::Close => unreachable!(),
+        }
+    });
+
     stroked_path.finish()
 }
 